@@ -1,50 +1,231 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::io::ErrorKind;
-use std::os::unix::ffi::OsStrExt;
 use std::path::{PathBuf,Path};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
 
+use caseless::default_case_fold_str;
 use lru::LruCache;
-use parking_lot::Mutex;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::event::ModifyKind;
+use parking_lot::{Mutex, RwLock};
+use tokio::fs as tfs;
+use unicode_normalization::UnicodeNormalization;
 
 lazy_static! {
-    static ref CACHE: Arc<Cache> = Arc::new(Cache::new(1024));
+    static ref CACHE: RwLock<Arc<Cache>> = RwLock::new(Arc::new(Cache::new(1024)));
+}
+
+// Swap out the cache `resolve`/`resolve_async`/`lookup`/`lookup_async` use,
+// e.g. to install one built with `Cache::with_watcher` instead of the
+// default plain `Cache::new`. Affects all callers in the process from then
+// on; there is no way to scope this to a single request.
+pub fn set_cache(cache: Arc<Cache>) {
+    *CACHE.write() = cache;
+}
+
+fn current_cache() -> Arc<Cache> {
+    CACHE.read().clone()
+}
+
+// Turn the raw WebDAV request path bytes into an `OsStr`. On Unix this is a
+// zero-copy reinterpretation; on Windows there is no byte-for-byte `OsStr`
+// constructor, so the (UTF-8, per the WebDAV/HTTP spec) bytes are decoded
+// and re-encoded into an `OsString` instead.
+#[cfg(unix)]
+fn os_str_from_bytes(bytes: &[u8]) -> Cow<'_, OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(OsStr::from_bytes(bytes))
+}
+
+// `from_utf8_lossy` replaces any malformed UTF-8 with U+FFFD rather than
+// round-tripping it losslessly (the way a real WTF-8 decode -- matching
+// what Windows' own `OsString` encoding can represent -- would). This is
+// an accepted tradeoff rather than an oversight: well-formed WebDAV
+// clients only ever send valid UTF-8 request paths, so a malformed
+// sequence here already indicates a broken or hostile client, and simply
+// can't address a file whose name contains that exact invalid byte
+// sequence on this platform.
+#[cfg(windows)]
+fn os_str_from_bytes(bytes: &[u8]) -> Cow<'static, OsStr> {
+    Cow::Owned(OsString::from(String::from_utf8_lossy(bytes).into_owned()))
+}
+
+// Windows filesystems in common use (NTFS, exFAT, FAT32) are natively
+// case-insensitive, so the OS already does the case folding for us there.
+// On those platforms we can skip the expensive directory-scan fallback
+// below entirely and let the OS resolve the path as given.
+#[cfg(windows)]
+const NATIVE_CASE_INSENSITIVE: bool = true;
+#[cfg(not(windows))]
+const NATIVE_CASE_INSENSITIVE: bool = false;
+
+// On macOS the kernel normalizes filenames to NFD before storing them
+// (HFS+/APFS), so a query segment received as NFC (the common form sent by
+// WebDAV clients) must be normalized before comparison, or "café" (NFC)
+// will never match an on-disk "café" (NFD). Filesystems that preserve
+// exactly the bytes they were given don't need this, but NFC-normalizing
+// an already-NFC string is a no-op, so it's harmless to leave on. This is
+// only a *default*: a binary built for one OS can still end up serving a
+// filesystem with different normalization behavior (a Linux box serving a
+// mounted HFS+/APFS volume, or a macOS build serving an ext4/NFS mount
+// that preserves names as given), so the real decision is a runtime flag
+// below, not the target OS.
+#[cfg(target_os = "macos")]
+const DEFAULT_NORMALIZE_NFC: bool = true;
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_NORMALIZE_NFC: bool = false;
+
+static NORMALIZE_NFC: AtomicBool = AtomicBool::new(DEFAULT_NORMALIZE_NFC);
+
+// Override whether filename comparisons NFC-normalize after case folding.
+// Defaults to `DEFAULT_NORMALIZE_NFC` for the build target; call this to
+// override it for the filesystem actually being served, e.g. turn it on
+// when serving a mounted HFS+/APFS volume from a Linux binary, or off when
+// a macOS build is serving a filesystem that preserves names exactly as
+// written.
+pub fn set_normalize_nfc(enabled: bool) {
+    NORMALIZE_NFC.store(enabled, Ordering::Relaxed);
+}
+
+// Compute a normalization- and case-insensitive comparison key for a
+// filename segment: full (non-locale) Unicode case folding -- so e.g.
+// "ß" folds to "ss", and the Turkish dotted/dotless-i is folded the same
+// as everywhere else -- optionally followed by NFC normalization, per
+// `set_normalize_nfc`.
+fn fold_key(s: &str) -> String {
+    let folded = default_case_fold_str(s);
+    if NORMALIZE_NFC.load(Ordering::Relaxed) {
+        folded.nfc().collect()
+    } else {
+        folded
+    }
+}
+
+// A normalized virtual path: an internal list of `/`-joined segments,
+// resolved against an empty virtual root. `.` segments are dropped, `..`
+// pops the last segment (or is refused if that would climb above the
+// root), and segments containing an embedded separator are refused
+// outright. Used to sanitize the incoming WebDAV request path before it is
+// ever turned into filesystem lookups, so a crafted `../../etc/passwd`
+// can never produce a `PathBuf` outside of `base`.
+#[derive(Default)]
+struct VfsPath {
+    segments: Vec<OsString>,
+}
+
+impl VfsPath {
+    fn new() -> VfsPath {
+        VfsPath { segments: Vec::new() }
+    }
+
+    // Normalize a `Path` (already relative or absolute) into a `VfsPath`.
+    // Returns `None` if any segment would climb above the virtual root.
+    fn from_bytes_path(path: &Path) -> Option<VfsPath> {
+        let mut vfs = VfsPath::new();
+        for seg in path.iter() {
+            vfs.push_segment(seg).ok()?;
+        }
+        Some(vfs)
+    }
+
+    // Push one segment, resolving `.` and `..` against the virtual root.
+    // Returns `Err` if the segment would escape the root, contains an
+    // embedded `/`, or (Windows only) contains an embedded `\` separator
+    // or a `:`. On Windows the root component arrives here as a lone `\`
+    // and is skipped like `/` is on Unix. `Path`'s own
+    // component parser has already split on the platform's separator(s)
+    // by the time a segment reaches here, so on Unix a literal `\` is
+    // just an ordinary (if unusual) filename byte, not something to
+    // reject. The `:` check matters because these segments are later
+    // pushed one at a time with `PathBuf::push`: on Windows, pushing a
+    // bare drive-letter-shaped component like `"C:"` -- an ordinary
+    // `Normal` component in the original request path, but freshly
+    // re-parsed as a `Prefix` once it's pushed in isolation -- makes
+    // `PathBuf::push` silently replace the whole path instead of
+    // appending, which would escape `base` entirely.
+    fn push_segment(&mut self, seg: &OsStr) -> Result<(), ()> {
+        if seg == "/" || seg == "." || seg.is_empty() {
+            return Ok(());
+        }
+        if seg == ".." {
+            return self.pop();
+        }
+        if seg.to_string_lossy().contains('/') {
+            return Err(());
+        }
+        #[cfg(windows)]
+        {
+            if seg == "\\" {
+                return Ok(());
+            }
+            if let Some(s) = seg.to_str() {
+                if s.contains('\\') || s.contains(':') {
+                    return Err(());
+                }
+            }
+        }
+        self.segments.push(seg.to_os_string());
+        Ok(())
+    }
+
+    // Pop the last segment. Refuses (rather than climbing above the root)
+    // if there is no segment left to pop.
+    fn pop(&mut self) -> Result<(), ()> {
+        match self.segments.pop() {
+            Some(_) => Ok(()),
+            None => Err(()),
+        }
+    }
+
+    fn into_segments(self) -> Vec<OsString> {
+        self.segments
+    }
 }
 
 // Do a case-insensitive path lookup.
 pub(crate) fn resolve<'a>(base: impl Into<PathBuf>, path: &[u8], case_insensitive: bool) -> PathBuf {
     let base = base.into();
-    let mut path = Path::new(OsStr::from_bytes(path));
+    let raw = os_str_from_bytes(path);
+    let path = Path::new(&raw);
 
-    // deref in advance: first lazy_static, then Arc.
-    let cache = &*(&*CACHE);
+    let cache = current_cache();
 
-    // make "path" relative.
-    while path.starts_with("/") {
-        path = match path.strip_prefix("/") {
-            Ok(p) => p,
-            Err(_) => break,
-        };
-    }
+    // Normalize the incoming path into a safe list of segments: drop `.`
+    // segments, resolve `..` against an empty virtual root, and refuse to
+    // climb above that root. A path that tries to escape (e.g.
+    // `../../etc/passwd`) resolves to `base` itself instead.
+    let segs = match VfsPath::from_bytes_path(path) {
+        Some(vfs) => vfs.into_segments(),
+        None => return base,
+    };
 
-    // if not case-mangling, return now.
-    if !case_insensitive {
+    // if not case-mangling, return now. On natively case-insensitive
+    // filesystems the OS already resolves case for us.
+    if !case_insensitive || NATIVE_CASE_INSENSITIVE {
         let mut newpath = base;
-        newpath.push(&path);
+        for seg in &segs {
+            newpath.push(seg);
+        }
         return newpath;
     }
 
     // must be rooted, and valid UTF-8.
     let mut fullpath = base.clone();
-    fullpath.push(&path);
+    for seg in &segs {
+        fullpath.push(seg);
+    }
     if !fullpath.has_root() || fullpath.to_str().is_none() {
         return fullpath;
     }
 
     // must have a parent.
     let parent = match fullpath.parent() {
-        Some(p) => p,
+        Some(p) => p.to_path_buf(),
         None => return fullpath,
     };
 
@@ -59,7 +240,6 @@ pub(crate) fn resolve<'a>(base: impl Into<PathBuf>, path: &[u8], case_insensitiv
     }
 
     // we need the path as a list of segments.
-    let segs = path.iter().collect::<Vec<_>>();
     if segs.len() == 0 {
         return fullpath;
     }
@@ -67,21 +247,21 @@ pub(crate) fn resolve<'a>(base: impl Into<PathBuf>, path: &[u8], case_insensitiv
     // if the parent exists, do a lookup there straight away
     // instead of starting from the root.
     let (parent, parent_exists) = if segs.len() > 1 {
-        match cache.get(parent) {
+        match cache.get(&parent) {
             Some((path, _)) => (path, true),
             None => {
                 let exists = parent.exists();
                 if exists {
-                    cache.insert(parent);
+                    cache.insert(&parent);
                 }
-                (parent.to_path_buf(), exists)
+                (parent, exists)
             },
         }
     } else {
-        (parent.to_path_buf(), true)
+        (parent, true)
     };
     if parent_exists {
-        let (newpath, stop) = lookup(parent, segs[segs.len() - 1], true);
+        let (newpath, stop) = lookup(parent, &segs[segs.len() - 1], true);
         if !stop {
             cache.insert(&newpath);
         }
@@ -98,7 +278,113 @@ pub(crate) fn resolve<'a>(base: impl Into<PathBuf>, path: &[u8], case_insensitiv
                 // Save the path leading up to this file or dir.
                 cache.insert(&newpath);
             }
-            let (n, s) = lookup(newpath, seg, false);
+            let (n, s) = lookup(newpath, &seg, false);
+            newpath = n;
+            stop = s;
+        } else {
+            newpath.push(seg);
+        }
+    }
+    if !stop {
+        // resolved succesfully. save in cache.
+        cache.insert(&newpath);
+    }
+    newpath
+}
+
+// Async version of `resolve`, for callers running on an async runtime who
+// don't want to block the executor thread on `fs::metadata` / `read_dir`.
+// Mirrors the sync version step for step; only the filesystem calls differ.
+pub(crate) async fn resolve_async<'a>(base: impl Into<PathBuf>, path: &[u8], case_insensitive: bool) -> PathBuf {
+    let base = base.into();
+    let raw = os_str_from_bytes(path);
+    let path = Path::new(&raw);
+
+    let cache = current_cache();
+
+    // Normalize the incoming path into a safe list of segments: drop `.`
+    // segments, resolve `..` against an empty virtual root, and refuse to
+    // climb above that root.
+    let segs = match VfsPath::from_bytes_path(path) {
+        Some(vfs) => vfs.into_segments(),
+        None => return base,
+    };
+
+    // if not case-mangling, return now. On natively case-insensitive
+    // filesystems the OS already resolves case for us.
+    if !case_insensitive || NATIVE_CASE_INSENSITIVE {
+        let mut newpath = base;
+        for seg in &segs {
+            newpath.push(seg);
+        }
+        return newpath;
+    }
+
+    // must be rooted, and valid UTF-8.
+    let mut fullpath = base.clone();
+    for seg in &segs {
+        fullpath.push(seg);
+    }
+    if !fullpath.has_root() || fullpath.to_str().is_none() {
+        return fullpath;
+    }
+
+    // must have a parent.
+    let parent = match fullpath.parent() {
+        Some(p) => p.to_path_buf(),
+        None => return fullpath,
+    };
+
+    // In the cache?
+    if let Some((path, _)) = cache.get_async(&fullpath).await {
+        return path;
+    }
+
+    // if the file exists, fine.
+    if tfs::metadata(&fullpath).await.is_ok() {
+        return fullpath;
+    }
+
+    // we need the path as a list of segments.
+    if segs.len() == 0 {
+        return fullpath;
+    }
+
+    // if the parent exists, do a lookup there straight away
+    // instead of starting from the root.
+    let (parent, parent_exists) = if segs.len() > 1 {
+        match cache.get_async(&parent).await {
+            Some((path, _)) => (path, true),
+            None => {
+                let exists = tfs::metadata(&parent).await.is_ok();
+                if exists {
+                    cache.insert(&parent);
+                }
+                (parent, exists)
+            },
+        }
+    } else {
+        (parent, true)
+    };
+    if parent_exists {
+        let (newpath, stop) = lookup_async(parent, &segs[segs.len() - 1], true).await;
+        if !stop {
+            cache.insert(&newpath);
+        }
+        return newpath;
+    }
+
+    // start from the root, then add segments one by one.
+    let mut stop = false;
+    let mut newpath = base;
+    let lastseg = segs.len() - 1;
+    for (idx, seg) in segs.into_iter().enumerate() {
+        if !stop {
+            if idx == lastseg {
+                // Save the path leading up to this file or dir.
+                cache.insert(&newpath);
+            }
+            let (n, s) = lookup_async(newpath, &seg, false).await;
             newpath = n;
             stop = s;
         } else {
@@ -129,9 +415,21 @@ fn lookup(mut path: PathBuf, seg: &OsStr, no_init_check: bool) -> (PathBuf, bool
         }
     }
 
-    // first, lowercase filename.
+    let cache = current_cache();
+
+    // Capture the directory's mtime so a negative result can be cached
+    // against it, and so an existing negative result can be validated.
+    let dir_mtime = path.metadata().ok().and_then(|m| m.modified().ok());
+    if let Some(mtime) = dir_mtime {
+        if cache.get_negative(&path, seg, mtime) {
+            // Known-absent as of this mtime: skip the directory scan.
+            return (path2, true);
+        }
+    }
+
+    // first, compute the fold key for the requested filename.
     let filename = match seg.to_str() {
-        Some(s) => s.to_lowercase(),
+        Some(s) => fold_key(s),
         None => return (path2, true),
     };
 
@@ -150,29 +448,109 @@ fn lookup(mut path: PathBuf, seg: &OsStr, no_init_check: bool) -> (PathBuf, bool
             Some(n) => n,
             None => continue,
         };
-        if name.to_lowercase() == filename {
+        if fold_key(name) == filename {
+            path.push(&name);
+            return (path, false);
+        }
+    }
+    if let Some(mtime) = dir_mtime {
+        cache.insert_negative(&path, seg, mtime);
+    }
+    (path2, true)
+}
+
+// Async version of `lookup`. Streams directory entries instead of
+// collecting them all up front, and returns as soon as a case-insensitive
+// match is found instead of scanning the rest of the directory.
+async fn lookup_async(mut path: PathBuf, seg: &OsStr, no_init_check: bool) -> (PathBuf, bool) {
+
+    // does it exist as-is?
+    let mut path2 = path.clone();
+    path2.push(seg);
+    if !no_init_check {
+        match tfs::metadata(&path2).await {
+            Ok(_) => return (path2, false),
+            Err(ref e) if e.kind() != ErrorKind::NotFound => {
+                // stop on errors other than "NotFound".
+                return (path2, true)
+            },
+            Err(_) => {},
+        }
+    }
+
+    let cache = current_cache();
+
+    // Capture the directory's mtime so a negative result can be cached
+    // against it, and so an existing negative result can be validated.
+    let dir_mtime = tfs::metadata(&path).await.ok().and_then(|m| m.modified().ok());
+    if let Some(mtime) = dir_mtime {
+        if cache.get_negative(&path, seg, mtime) {
+            // Known-absent as of this mtime: skip the directory scan.
+            return (path2, true);
+        }
+    }
+
+    // first, compute the fold key for the requested filename.
+    let filename = match seg.to_str() {
+        Some(s) => fold_key(s),
+        None => return (path2, true),
+    };
+
+    // we have to read the directory, but we can stop at the first match.
+    let mut dir = match tfs::read_dir(&path).await {
+        Ok(dir) => dir,
+        Err(_) => return (path2, true),
+    };
+    loop {
+        let entry = match dir.next_entry().await {
+            Ok(Some(e)) => e,
+            Ok(None) => break,
+            Err(_) => break,
+        };
+        let entry_name = entry.file_name();
+        let name = match entry_name.to_str() {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        if fold_key(&name) == filename {
             path.push(&name);
             return (path, false);
         }
     }
+    if let Some(mtime) = dir_mtime {
+        cache.insert_negative(&path, seg, mtime);
+    }
     (path2, true)
 }
 
-// The cache stores a mapping of lowercased path -> actual path.
+// The cache stores a mapping of folded+normalized path -> actual path, or
+// -> a marker that the path is known not to exist (see `Entry`).
 pub struct Cache {
     cache:      Mutex<LruCache<PathBuf, Entry>>,
+    // Directories currently watched, so we don't register the same watch
+    // twice. Empty (and `watcher` is `None`) unless built via `with_watcher`.
+    watched:    Mutex<HashSet<PathBuf>>,
+    watcher:    Mutex<Option<RecommendedWatcher>>,
 }
 
 #[derive(Clone)]
-struct Entry {
-    // Full case-sensitive pathname.
-    path:   PathBuf,
+enum Entry {
+    // A segment resolved successfully; holds the full case-sensitive
+    // pathname it resolved to.
+    Found(PathBuf),
+    // A segment is known *not* to exist in its containing directory, as of
+    // `dir_mtime`. Valid only as long as the directory's mtime hasn't
+    // advanced past that point; once it has, the directory may have
+    // gained the entry and the negative result must be treated as stale.
+    NotFound { dir_mtime: SystemTime },
 }
 
-// helper
-fn pathbuf_to_lowercase(path: PathBuf) -> PathBuf {
+// Compute the cache key for a path: its fold key, so that cache hits are
+// case- and normalization-insensitive just like the lookups that populate
+// the cache.
+fn pathbuf_to_foldkey(path: PathBuf) -> PathBuf {
     let s = match OsString::from(path).into_string() {
-        Ok(s) => OsString::from(s.to_lowercase()),
+        Ok(s) => OsString::from(fold_key(&s)),
         Err(s) => s,
     };
     PathBuf::from(s)
@@ -180,38 +558,349 @@ fn pathbuf_to_lowercase(path: PathBuf) -> PathBuf {
 
 impl Cache {
     pub fn new(size: usize) -> Cache {
-        Cache{ cache: Mutex::new(LruCache::new(size)) }
+        Cache {
+            cache:      Mutex::new(LruCache::new(size)),
+            watched:    Mutex::new(HashSet::new()),
+            watcher:    Mutex::new(None),
+        }
+    }
+
+    // Like `new`, but also starts an inotify-backed (via `notify`) watcher
+    // on every directory this cache ends up resolving into. When a watched
+    // directory reports a rename or remove -- the case that matters here,
+    // since on a case-insensitive filesystem the "realname" of an entry can
+    // change without the path itself changing -- the affected cache key and
+    // all of its descendant keys are evicted proactively, instead of
+    // waiting for the next lazy `get`-time revalidation. Callers who don't
+    // want the extra background thread and file descriptors should keep
+    // using `new`.
+    pub fn with_watcher(size: usize) -> Arc<Cache> {
+        let cache = Arc::new(Cache {
+            cache:      Mutex::new(LruCache::new(size)),
+            watched:    Mutex::new(HashSet::new()),
+            watcher:    Mutex::new(None),
+        });
+
+        let watch_cache = Arc::clone(&cache);
+        let handler = move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(e) => e,
+                Err(_) => return,
+            };
+            match event.kind {
+                EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)) => {
+                    for path in &event.paths {
+                        watch_cache.evict_subtree(path);
+                    }
+                },
+                _ => {},
+            }
+        };
+        if let Ok(watcher) = RecommendedWatcher::new(handler, notify::Config::default()) {
+            *cache.watcher.lock() = Some(watcher);
+        }
+        cache
     }
 
-    // Insert an entry into the cache.
+    // Insert an entry into the cache, and -- if this cache was built with
+    // `with_watcher` -- start watching its parent directory for renames
+    // and removals.
     pub fn insert(&self, path: &Path) {
-        let lc_path = pathbuf_to_lowercase(PathBuf::from(path));
-        let e = Entry {
-            path:   PathBuf::from(path),
+        let lc_path = pathbuf_to_foldkey(PathBuf::from(path));
+        let e = Entry::Found(PathBuf::from(path));
+        {
+            let mut cache = self.cache.lock();
+            cache.put(lc_path, e);
+        }
+        self.watch_parent(path);
+    }
+
+    // Record that `seg` does not exist in `dir`, as of `dir_mtime`. Shares
+    // the same cache map and key scheme (fold key of the candidate path)
+    // as the positive entries inserted by `insert`.
+    fn insert_negative(&self, dir: &Path, seg: &OsStr, dir_mtime: SystemTime) {
+        let mut candidate = dir.to_path_buf();
+        candidate.push(seg);
+        let key = pathbuf_to_foldkey(candidate);
+        let mut cache = self.cache.lock();
+        cache.put(key, Entry::NotFound { dir_mtime });
+    }
+
+    // Check whether `seg` is known not to exist in `dir`. Only valid as
+    // long as `dir_mtime` (the directory's *current* mtime) matches the
+    // mtime captured when the negative entry was recorded; a stale entry
+    // is evicted and treated as a miss.
+    fn get_negative(&self, dir: &Path, seg: &OsStr, dir_mtime: SystemTime) -> bool {
+        let mut candidate = dir.to_path_buf();
+        candidate.push(seg);
+        let key = pathbuf_to_foldkey(candidate);
+        let mut cache = self.cache.lock();
+        match cache.get(&key) {
+            Some(Entry::NotFound { dir_mtime: cached }) if *cached == dir_mtime => true,
+            Some(Entry::NotFound { .. }) => {
+                cache.pop(&key);
+                false
+            },
+            _ => false,
+        }
+    }
+
+    // Start watching `path`'s parent directory, if we have a watcher and
+    // aren't watching it already.
+    fn watch_parent(&self, path: &Path) {
+        let mut watcher = self.watcher.lock();
+        let watcher = match watcher.as_mut() {
+            Some(w) => w,
+            None => return,
         };
+        let parent = match path.parent() {
+            Some(p) => p,
+            None => return,
+        };
+        let mut watched = self.watched.lock();
+        if watched.insert(parent.to_path_buf()) {
+            let _ = watcher.watch(parent, RecursiveMode::NonRecursive);
+        }
+    }
+
+    // Evict `path` and every cache entry nested under it (by fold key).
+    // Used when the watcher reports that `path` was renamed or removed.
+    fn evict_subtree(&self, path: &Path) {
+        let prefix = pathbuf_to_foldkey(path.to_path_buf());
         let mut cache = self.cache.lock();
-        cache.put(lc_path, e);
+        let victims: Vec<PathBuf> = cache
+            .iter()
+            .map(|(k, _)| k.clone())
+            .filter(|k| *k == prefix || k.starts_with(&prefix))
+            .collect();
+        for key in victims {
+            cache.pop(&key);
+        }
     }
 
     // Get an entry from the cache, and validate it. If it's valid
     // return the actual pathname and metadata. If it's invalid remove
     // it from the cache and return None.
     pub fn get(&self, path: &Path) -> Option<(PathBuf, fs::Metadata)> {
-        // First lowercase the entire path.
-        let lc_path = pathbuf_to_lowercase(PathBuf::from(path));
+        // First compute the fold key for the entire path.
+        let lc_path = pathbuf_to_foldkey(PathBuf::from(path));
         // Lookup.
         let e = {
             let mut cache = self.cache.lock();
             cache.get(&lc_path)?.clone()
         };
+        let found_path = match e {
+            Entry::Found(p) => p,
+            Entry::NotFound { .. } => return None,
+        };
         // Found, validate.
-        match fs::metadata(&e.path) {
+        match fs::metadata(&found_path) {
             Err(_) => {
                 let mut cache = self.cache.lock();
                 cache.pop(&lc_path);
                 None
             }
-            Ok(m) => Some((e.path, m))
+            Ok(m) => Some((found_path, m))
         }
     }
+
+    // Async version of `get`, for callers on an async runtime. Validation
+    // goes through `tokio::fs::metadata` instead of the blocking `fs::metadata`.
+    pub async fn get_async(&self, path: &Path) -> Option<(PathBuf, fs::Metadata)> {
+        // First compute the fold key for the entire path.
+        let lc_path = pathbuf_to_foldkey(PathBuf::from(path));
+        // Lookup.
+        let e = {
+            let mut cache = self.cache.lock();
+            cache.get(&lc_path)?.clone()
+        };
+        let found_path = match e {
+            Entry::Found(p) => p,
+            Entry::NotFound { .. } => return None,
+        };
+        // Found, validate.
+        match tfs::metadata(&found_path).await {
+            Err(_) => {
+                let mut cache = self.cache.lock();
+                cache.pop(&lc_path);
+                None
+            }
+            Ok(m) => Some((found_path, m))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn vfs_path_rejects_traversal_above_root() {
+        assert!(VfsPath::from_bytes_path(Path::new("../etc/passwd")).is_none());
+        assert!(VfsPath::from_bytes_path(Path::new("a/../../b")).is_none());
+    }
+
+    #[test]
+    fn vfs_path_resolves_dot_and_dotdot_within_root() {
+        let vfs = VfsPath::from_bytes_path(Path::new("a/./b/../c")).unwrap();
+        assert_eq!(
+            vfs.into_segments(),
+            vec![OsString::from("a"), OsString::from("c")],
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn vfs_path_allows_literal_backslash_in_unix_filename() {
+        let vfs = VfsPath::from_bytes_path(Path::new("report\\2024.txt")).unwrap();
+        assert_eq!(vfs.into_segments(), vec![OsString::from("report\\2024.txt")]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn vfs_path_rejects_embedded_backslash_on_windows() {
+        let mut vfs = VfsPath::new();
+        assert!(vfs.push_segment(OsStr::new("a\\b")).is_err());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn vfs_path_rejects_drive_letter_prefix_segment_on_windows() {
+        let mut vfs = VfsPath::new();
+        assert!(vfs.push_segment(OsStr::new("C:")).is_err());
+        assert!(vfs.push_segment(OsStr::new("c:")).is_err());
+    }
+
+    #[test]
+    fn fold_key_folds_sharp_s_like_ss() {
+        assert_eq!(fold_key("straße"), fold_key("strasse"));
+    }
+
+    #[test]
+    fn fold_key_folds_final_sigma_like_sigma() {
+        assert_eq!(fold_key("ς"), fold_key("σ"));
+    }
+
+    #[test]
+    fn fold_key_uses_default_non_locale_folding_for_turkish_dotted_i() {
+        // Default (non-locale) case folding maps U+0130 to "i" + a
+        // combining dot above (U+0307), not the plain "i" a Turkish-locale
+        // fold would produce.
+        assert_ne!(fold_key("İ"), "i");
+    }
+
+    #[test]
+    fn negative_cache_hit_is_valid_while_dir_mtime_unchanged() {
+        let cache = Cache::new(8);
+        let dir = Path::new("/tmp/fspath-test-dir");
+        let seg = OsStr::new("missing.txt");
+        let mtime = SystemTime::now();
+
+        assert!(!cache.get_negative(dir, seg, mtime));
+        cache.insert_negative(dir, seg, mtime);
+        assert!(cache.get_negative(dir, seg, mtime));
+    }
+
+    #[test]
+    fn negative_cache_is_invalidated_once_dir_mtime_advances() {
+        let cache = Cache::new(8);
+        let dir = Path::new("/tmp/fspath-test-dir2");
+        let seg = OsStr::new("missing.txt");
+        let mtime = SystemTime::now();
+
+        cache.insert_negative(dir, seg, mtime);
+        assert!(cache.get_negative(dir, seg, mtime));
+
+        let later = mtime + Duration::from_secs(1);
+        assert!(!cache.get_negative(dir, seg, later));
+    }
+
+    #[test]
+    fn evict_subtree_removes_exact_and_descendant_keys() {
+        let cache = Cache::new(8);
+        cache.insert(Path::new("/tmp/fspath-evict/dir"));
+        cache.insert(Path::new("/tmp/fspath-evict/dir/file.txt"));
+        cache.insert(Path::new("/tmp/fspath-evict-other/file.txt"));
+
+        cache.evict_subtree(Path::new("/tmp/fspath-evict/dir"));
+
+        let mut locked = cache.cache.lock();
+        assert!(locked.get(&pathbuf_to_foldkey(PathBuf::from("/tmp/fspath-evict/dir"))).is_none());
+        assert!(locked.get(&pathbuf_to_foldkey(PathBuf::from("/tmp/fspath-evict/dir/file.txt"))).is_none());
+        assert!(locked.get(&pathbuf_to_foldkey(PathBuf::from("/tmp/fspath-evict-other/file.txt"))).is_some());
+    }
+
+    #[test]
+    fn watch_parent_registers_each_directory_once() {
+        let cache = Cache::with_watcher(8);
+        // Some sandboxes have no inotify (or equivalent) available; skip
+        // rather than fail if the watcher couldn't be created.
+        if cache.watcher.lock().is_none() {
+            return;
+        }
+        let dir = std::env::temp_dir();
+        let a = dir.join("fspath-watch-test-a.txt");
+        let b = dir.join("fspath-watch-test-b.txt");
+
+        cache.watch_parent(&a);
+        cache.watch_parent(&b);
+
+        assert_eq!(cache.watched.lock().len(), 1);
+        assert!(cache.watched.lock().contains(&dir));
+    }
+
+    #[test]
+    fn set_cache_swaps_the_cache_resolve_and_lookup_use() {
+        let custom = Arc::new(Cache::new(4));
+        set_cache(Arc::clone(&custom));
+        assert!(Arc::ptr_eq(&current_cache(), &custom));
+
+        // restore a fresh default so later tests don't depend on a custom
+        // cache left behind by this one.
+        set_cache(Arc::new(Cache::new(1024)));
+    }
+
+    #[test]
+    fn os_str_from_bytes_round_trips_utf8() {
+        let raw = os_str_from_bytes("café".as_bytes());
+        assert_eq!(raw.to_str(), Some("café"));
+    }
+
+    #[test]
+    fn native_case_insensitive_matches_target_platform() {
+        assert_eq!(NATIVE_CASE_INSENSITIVE, cfg!(windows));
+    }
+
+    fn unique_temp_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("fspath-{}-{:?}", name, std::thread::current().id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lookup_finds_case_insensitive_match_via_directory_scan() {
+        let dir = unique_temp_dir("lookup-sync");
+        fs::File::create(dir.join("Report.TXT")).unwrap();
+
+        let (path, stop) = lookup(dir.clone(), OsStr::new("report.txt"), false);
+        assert!(!stop);
+        assert_eq!(path, dir.join("Report.TXT"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn lookup_async_finds_case_insensitive_match_via_directory_scan() {
+        let dir = unique_temp_dir("lookup-async");
+        fs::File::create(dir.join("Report.TXT")).unwrap();
+
+        let (path, stop) = lookup_async(dir.clone(), OsStr::new("report.txt"), false).await;
+        assert!(!stop);
+        assert_eq!(path, dir.join("Report.TXT"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }